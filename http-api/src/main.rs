@@ -7,13 +7,86 @@ use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use rustls::{ClientConfig, RootCertStore};
-use rustls::pki_types::{CertificateDer, pem::PemObject};
+use std::time::Duration;
 use futures::{TryStreamExt};
-use std::io::BufReader;
-use std::fs::File;
-use rustls_pemfile::certs;
-use anyhow::{Context};
+
+mod tls;
+
+// Default per-endpoint timeouts (milliseconds). Single-row writes get a tight
+// budget; batch inserts are allowed to run longer.
+const DEFAULT_INSERT_TIMEOUT_MS: u64 = 5_000;
+const DEFAULT_BATCH_TIMEOUT_MS: u64 = 30_000;
+const DEFAULT_QUERY_TIMEOUT_MS: u64 = 10_000;
+
+// Outcome of a DB call run under the timeout guard.
+enum GuardError {
+    // The deadline elapsed before the DB future completed; it was aborted.
+    Timeout,
+    // The spawned task panicked.
+    Panicked,
+}
+
+// Run a database future with a hard deadline. The future is spawned together
+// with an `AbortHandle` and raced against a `tokio::time::timeout`; if the
+// deadline fires first the future is aborted so the connection is freed instead
+// of being pinned by a stuck node.
+async fn run_guarded<F, T>(fut: F, deadline: Duration) -> Result<T, GuardError>
+where
+    F: std::future::Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    let (task, abort_handle) = futures::future::abortable(fut);
+    let join = tokio::spawn(task);
+
+    match tokio::time::timeout(deadline, join).await {
+        Ok(Ok(Ok(output))) => Ok(output),
+        // The abortable resolved to Aborted — only happens if we aborted it.
+        Ok(Ok(Err(_aborted))) => Err(GuardError::Timeout),
+        Ok(Err(_join_err)) => Err(GuardError::Panicked),
+        Err(_elapsed) => {
+            abort_handle.abort();
+            Err(GuardError::Timeout)
+        }
+    }
+}
+
+// Resolve the effective deadline for a request: a `timeout_ms` query parameter
+// overrides the endpoint's env var, which in turn overrides the default.
+fn resolve_timeout(req: &Request<Body>, env_var: &str, default_ms: u64) -> Duration {
+    if let Some(ms) = query_param(req.uri().query(), "timeout_ms").and_then(|v| v.parse::<u64>().ok())
+    {
+        return Duration::from_millis(ms);
+    }
+    if let Some(ms) = std::env::var(env_var).ok().and_then(|v| v.parse::<u64>().ok()) {
+        return Duration::from_millis(ms);
+    }
+    Duration::from_millis(default_ms)
+}
+
+// Pull a single value out of a URL query string (e.g. `a=1&timeout_ms=500`).
+fn query_param(query: Option<&str>, key: &str) -> Option<String> {
+    query?.split('&').find_map(|pair| {
+        let (k, v) = pair.split_once('=')?;
+        (k == key).then(|| v.to_string())
+    })
+}
+
+// Build a 504 Gateway Timeout response with a structured JSON error body.
+fn timeout_response(deadline: Duration) -> Response<Body> {
+    let body = serde_json::json!({
+        "success": false,
+        "error": "timeout",
+        "message": format!("database operation exceeded the {} ms deadline", deadline.as_millis()),
+    })
+    .to_string();
+    let mut resp = Response::new(Body::from(body));
+    *resp.status_mut() = StatusCode::GATEWAY_TIMEOUT;
+    resp.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    resp
+}
 
 // Holds a shared Session inside an Arc so the HTTP service 
 // can clone this state cheaply between requests.
@@ -46,45 +119,17 @@ async fn main() -> anyhow::Result<()> {
 
     // Build a SessionBuilder so we can optionally configure TLS.
     let mut builder = SessionBuilder::new()
-        // .known_node(uri.clone());        
+        // .known_node(uri.clone());
         .known_node("127.0.0.2:9042")
         .known_node("127.0.0.3:9042")
         .known_node("127.0.0.4:9042");
 
     println!("Connecting to ScyllaDB");
 
-    // If SCYLLA_USE_TLS=1 enable TLS with rustls.
-    // For now TLS connection doesnt work.
-    // if std::env::var("SCYLLA_USE_TLS").unwrap_or_default() == "1" {
-        println!("Loading TLS root certificates from SCYLLA_TLS_CA...");
-
-        let mut root_store = RootCertStore::empty();
-
-        // Load CA certificate
-        let ca_file = File::open("certs/ca.crt")?;
-        let mut ca_reader = BufReader::new(ca_file);
-
-        // Read certificates from PEM file
-        let mut cert_count = 0;
-        for cert in certs(&mut ca_reader) {
-            let cert = cert.context("Failed to parse certificate from ca.crt")?;
-            root_store.add(cert)?;
-            cert_count += 1;
-        }
-
-        if cert_count == 0 {
-            anyhow::bail!("No certificates found in certs/ca.crt");
-        }
-
-
-        // let rustls_ca = CertificateDer::from_pem_file("certs/ca.crt")?;
-        // root_store.add(rustls_ca)?;
-        let config = ClientConfig::builder()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
-
-        builder = builder.tls_context(Some(std::sync::Arc::new(config)));
-    // }
+    // Enable TLS when SCYLLA_USE_TLS=1, using the shared env-driven config.
+    if let Some(config) = tls::build_client_config()? {
+        builder = builder.tls_context(Some(config));
+    }
 
     let session: Session = builder.build().await?;
 
@@ -127,13 +172,32 @@ async fn handle(req: Request<Body>, state: Arc<AppState>) -> Result<Response<Bod
     let path = req.uri().path().to_string();
     match (req.method(), path.as_str()) {
         (&Method::POST, "/insert") => {
+            let deadline = resolve_timeout(&req, "SCYLLA_TIMEOUT_INSERT_MS", DEFAULT_INSERT_TIMEOUT_MS);
             let whole = hyper::body::to_bytes(req.into_body()).await.unwrap_or_default();
             match serde_json::from_slice::<Item>(&whole) {
                 Ok(item) => {
-                    let cql = "INSERT INTO demo.items (id, name, value) VALUES (?, ?, ?)";
-                    let _ = state.session.query_unpaged(cql, (item.id, item.name, item.value)).await;
-                    let body = serde_json::to_string(&InsertResponse { success: true }).unwrap();
-                    Ok(Response::new(Body::from(body)))
+                    let session = state.session.clone();
+                    let fut = async move {
+                        let cql = "INSERT INTO demo.items (id, name, value) VALUES (?, ?, ?)";
+                        session.query_unpaged(cql, (item.id, item.name, item.value)).await
+                    };
+                    match run_guarded(fut, deadline).await {
+                        Ok(Ok(_)) => {
+                            let body = serde_json::to_string(&InsertResponse { success: true }).unwrap();
+                            Ok(Response::new(Body::from(body)))
+                        }
+                        Ok(Err(e)) => {
+                            let mut resp = Response::new(Body::from(format!("db error: {}", e)));
+                            *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                            Ok(resp)
+                        }
+                        Err(GuardError::Timeout) => Ok(timeout_response(deadline)),
+                        Err(GuardError::Panicked) => {
+                            let mut resp = Response::new(Body::from("internal error"));
+                            *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                            Ok(resp)
+                        }
+                    }
                 }
                 Err(e) => {
                     let mut resp = Response::new(Body::from(format!("invalid json: {}", e)));
@@ -143,6 +207,7 @@ async fn handle(req: Request<Body>, state: Arc<AppState>) -> Result<Response<Bod
             }
         }
         (&Method::POST, "/insert_batch") => {
+            let deadline = resolve_timeout(&req, "SCYLLA_TIMEOUT_BATCH_MS", DEFAULT_BATCH_TIMEOUT_MS);
             let whole = hyper::body::to_bytes(req.into_body()).await.unwrap_or_default();
             match serde_json::from_slice::<Vec<Item>>(&whole) {
                 Ok(items) => {
@@ -157,17 +222,26 @@ async fn handle(req: Request<Body>, state: Arc<AppState>) -> Result<Response<Bod
                         values_vec.push((item.id, item.name, item.value));
                     }
 
-                    // Execute the typed batch. The Vec<T> where T: SerializeRow implements BatchValues.
-                    match state.session.batch(&batch, values_vec).await {
-                        Ok(_) => {
+                    // Execute the typed batch under the timeout guard. The Vec<T>
+                    // where T: SerializeRow implements BatchValues.
+                    let session = state.session.clone();
+                    let fut = async move { session.batch(&batch, values_vec).await };
+                    match run_guarded(fut, deadline).await {
+                        Ok(Ok(_)) => {
                             let body = serde_json::to_string(&InsertResponse { success: true }).unwrap();
                             Ok(Response::new(Body::from(body)))
                         }
-                        Err(e) => {
+                        Ok(Err(e)) => {
                             let mut resp = Response::new(Body::from(format!("batch error: {}", e)));
                             *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
                             Ok(resp)
                         }
+                        Err(GuardError::Timeout) => Ok(timeout_response(deadline)),
+                        Err(GuardError::Panicked) => {
+                            let mut resp = Response::new(Body::from("internal error"));
+                            *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                            Ok(resp)
+                        }
                     }
                 }
                 Err(e) => {
@@ -204,29 +278,50 @@ async fn handle(req: Request<Body>, state: Arc<AppState>) -> Result<Response<Bod
             }
         }
         (&Method::GET, "/query_iter") => {
-            // Run query_iter which returns a pager/iterator that streams rows
-            match state.session.query_iter("SELECT id, name, value FROM demo.items", ()).await {
-                Ok(pager) => {
-                    match pager.rows_stream::<(uuid::Uuid, String, i64)>() {
-                        Ok(mut rows_stream) => {
-                            let mut out = Vec::new();
-                            // rows_stream is a TryStream of Result<Row, Error>
-                            while let Some(row_res) = rows_stream.try_next().await.unwrap_or(None) {
-                                let (id, name, value) = row_res;
-                                out.push(serde_json::json!({"id": id.to_string(), "name": name, "value": value}));
-                            }
-                            let body = serde_json::to_string(&serde_json::json!({"rows": out})).unwrap_or_else(|_| "{}".to_string());
-                            Ok(Response::new(Body::from(body)))
-                        }
-                        Err(e) => {
-                            let mut resp = Response::new(Body::from(format!("failed to get rows_stream: {}", e)));
-                            *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
-                            Ok(resp)
-                        }
-                    }
+            let deadline = resolve_timeout(&req, "SCYLLA_TIMEOUT_QUERY_MS", DEFAULT_QUERY_TIMEOUT_MS);
+
+            // Run query_iter (which streams rows) under the timeout guard. The
+            // whole fetch-and-collect is moved into the spawned future so an
+            // aborted paging loop releases the connection.
+            let session = state.session.clone();
+            let fut = async move {
+                let pager = session
+                    .query_iter("SELECT id, name, value FROM demo.items", ())
+                    .await
+                    .map_err(|e| format!("query_iter error: {}", e))?;
+                let mut rows_stream = pager
+                    .rows_stream::<(uuid::Uuid, String, i64)>()
+                    .map_err(|e| format!("failed to get rows_stream: {}", e))?;
+
+                let mut out = Vec::new();
+                // rows_stream is a TryStream of Result<Row, Error>. Propagate a
+                // mid-stream paging error instead of treating it as end-of-stream,
+                // so a partial result set never masquerades as a 200 success.
+                while let Some(row_res) = rows_stream
+                    .try_next()
+                    .await
+                    .map_err(|e| format!("rows_stream error: {}", e))?
+                {
+                    let (id, name, value) = row_res;
+                    out.push(serde_json::json!({"id": id.to_string(), "name": name, "value": value}));
                 }
-                Err(e) => {
-                    let mut resp = Response::new(Body::from(format!("query_iter error: {}", e)));
+                Ok::<_, String>(out)
+            };
+
+            match run_guarded(fut, deadline).await {
+                Ok(Ok(out)) => {
+                    let body = serde_json::to_string(&serde_json::json!({"rows": out}))
+                        .unwrap_or_else(|_| "{}".to_string());
+                    Ok(Response::new(Body::from(body)))
+                }
+                Ok(Err(e)) => {
+                    let mut resp = Response::new(Body::from(e));
+                    *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                    Ok(resp)
+                }
+                Err(GuardError::Timeout) => Ok(timeout_response(deadline)),
+                Err(GuardError::Panicked) => {
+                    let mut resp = Response::new(Body::from("internal error"));
                     *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
                     Ok(resp)
                 }
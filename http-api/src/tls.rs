@@ -0,0 +1,441 @@
+//! Shared, environment-driven TLS setup for both binaries.
+//!
+//! A single [`build_client_config`] reads the `SCYLLA_USE_TLS`,
+//! `SCYLLA_TLS_CA`, `SCYLLA_TLS_CLIENT_CERT` and `SCYLLA_TLS_CLIENT_KEY`
+//! variables so plaintext vs. TLS and the certificate material are selected at
+//! runtime instead of by editing source. Server-certificate verification is
+//! pluggable via `SCYLLA_TLS_VERIFY` (`insecure` or `pinned`).
+
+use anyhow::{Context, Result};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use rustls_pemfile::{certs, private_key};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+
+/// Path to the CA bundle, overridable via `SCYLLA_TLS_CA`.
+pub fn ca_path() -> String {
+    std::env::var("SCYLLA_TLS_CA").unwrap_or_else(|_| "certs/ca.crt".to_string())
+}
+
+/// Path to the client certificate chain, overridable via `SCYLLA_TLS_CLIENT_CERT`.
+pub fn client_cert_path() -> String {
+    std::env::var("SCYLLA_TLS_CLIENT_CERT").unwrap_or_else(|_| "certs/client.crt".to_string())
+}
+
+/// Path to the client private key, overridable via `SCYLLA_TLS_CLIENT_KEY`.
+pub fn client_key_path() -> String {
+    std::env::var("SCYLLA_TLS_CLIENT_KEY").unwrap_or_else(|_| "certs/client.key".to_string())
+}
+
+/// Build the rustls client configuration from the environment.
+///
+/// Returns `Ok(None)` when `SCYLLA_USE_TLS` is not `1`, so callers connect in
+/// plaintext without special-casing. Otherwise the CA trust, optional client
+/// certificate and server-verification mode are assembled from the environment.
+pub fn build_client_config() -> Result<Option<Arc<ClientConfig>>> {
+    if std::env::var("SCYLLA_USE_TLS").unwrap_or_default() != "1" {
+        return Ok(None);
+    }
+
+    // Server-certificate verification is pluggable. By default we trust the CA
+    // bundle, but `SCYLLA_TLS_VERIFY` switches in a custom verifier for dev
+    // clusters (self-signed) or pinned deployments whose CA isn't available.
+    let builder = match std::env::var("SCYLLA_TLS_VERIFY").as_deref() {
+        Ok("insecure") => {
+            println!("⚠ SCYLLA_TLS_VERIFY=insecure: accepting ANY server certificate (dev only)");
+            ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(InsecureServerVerifier::new()))
+        }
+        Ok("pinned") => {
+            let fingerprint = std::env::var("SCYLLA_TLS_PINNED_SHA256").context(
+                "SCYLLA_TLS_VERIFY=pinned requires SCYLLA_TLS_PINNED_SHA256 (hex SHA-256 of the leaf certificate)",
+            )?;
+            let verifier = PinnedServerVerifier::new(&fingerprint)?;
+            println!(
+                "✓ Pinning server leaf certificate to SHA-256 {}",
+                verifier.fingerprint_hex()
+            );
+            ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(verifier))
+        }
+        _ => {
+            let root_store = load_root_store()?;
+            ClientConfig::builder().with_root_certificates(root_store)
+        }
+    };
+
+    // Optionally authenticate to the cluster with a client certificate.
+    // Both files must be present together; having only one is a misconfiguration.
+    let config = match load_client_auth()? {
+        Some((chain, key)) => builder
+            .with_client_auth_cert(chain, key)
+            .context("Failed to configure client certificate authentication")?,
+        None => builder.with_no_client_auth(),
+    };
+
+    Ok(Some(Arc::new(config)))
+}
+
+/// Build a root certificate store from the configured CA material.
+///
+/// The CA may come from a file path, an inline PEM string, or a DER blob — see
+/// [`read_material`] for how the source is resolved.
+pub fn load_root_store() -> Result<RootCertStore> {
+    let bytes = read_material("SCYLLA_TLS_CA", "certs/ca.crt")?;
+    let mut root_store = RootCertStore::empty();
+
+    let mut cert_count = 0;
+    for cert in certs_from_bytes(&bytes)? {
+        root_store
+            .add(cert)
+            .context("Failed to add certificate to root store")?;
+        cert_count += 1;
+    }
+
+    if cert_count == 0 {
+        anyhow::bail!("No CA certificates found in SCYLLA_TLS_CA / certs/ca.crt");
+    }
+
+    println!("✓ Loaded {cert_count} CA certificate(s)");
+
+    Ok(root_store)
+}
+
+/// Resolve TLS material from one of three sources, in order of preference:
+///
+/// 1. an inline PEM string in `env_var` (detected by a `-----BEGIN` header),
+/// 2. a filesystem path in `env_var` (PEM or DER contents), or
+/// 3. the `default_path` on disk.
+fn read_material(env_var: &str, default_path: &str) -> Result<Vec<u8>> {
+    match std::env::var(env_var) {
+        Ok(v) if is_pem(v.as_bytes()) => Ok(v.into_bytes()),
+        Ok(v) => std::fs::read(&v).with_context(|| format!("Failed to read {env_var} file {v}")),
+        Err(_) => {
+            std::fs::read(default_path).with_context(|| format!("Failed to read {default_path}"))
+        }
+    }
+}
+
+/// Is this material PEM-encoded? PEM carries a `-----BEGIN` armor header; DER is
+/// raw ASN.1 and never contains it.
+fn is_pem(bytes: &[u8]) -> bool {
+    bytes.windows(10).any(|w| w == b"-----BEGIN")
+}
+
+/// Parse certificates from either PEM or raw DER bytes.
+fn certs_from_bytes(bytes: &[u8]) -> Result<Vec<CertificateDer<'static>>> {
+    if is_pem(bytes) {
+        collect_certs(BufReader::new(bytes))
+    } else {
+        Ok(vec![CertificateDer::from(bytes.to_vec())])
+    }
+}
+
+/// Collect every certificate from a PEM source. Generic over the reader so the
+/// same parsing path serves files and inline byte buffers alike.
+fn collect_certs(mut reader: impl BufRead) -> Result<Vec<CertificateDer<'static>>> {
+    certs(&mut reader)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .context("Failed to parse certificate(s)")
+}
+
+/// Parse a private key from either PEM or raw DER bytes.
+fn key_from_bytes(bytes: &[u8]) -> Result<PrivateKeyDer<'static>> {
+    if is_pem(bytes) {
+        private_key(&mut BufReader::new(bytes))
+            .context("Failed to parse private key")?
+            .context("No private key found")
+    } else {
+        PrivateKeyDer::try_from(bytes.to_vec())
+            .map_err(|e| anyhow::anyhow!("Failed to parse DER private key: {e}"))
+    }
+}
+
+/// Is client material available from either an env var or the default file?
+fn material_present(env_var: &str, default_path: &str) -> bool {
+    std::env::var_os(env_var).is_some() || std::path::Path::new(default_path).exists()
+}
+
+/// Load the client certificate chain and private key for mutual TLS, if present.
+///
+/// Returns `None` when neither file exists (server-side TLS only) and the fully
+/// parsed chain/key when both exist. If exactly one of the two is present the
+/// configuration is ambiguous, so we fail loudly rather than silently dropping
+/// client auth — clusters with `require_client_auth: true` would otherwise reject
+/// the connection with a confusing handshake error.
+pub fn load_client_auth(
+) -> Result<Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>> {
+    let cert_present = material_present("SCYLLA_TLS_CLIENT_CERT", "certs/client.crt");
+    let key_present = material_present("SCYLLA_TLS_CLIENT_KEY", "certs/client.key");
+
+    match (cert_present, key_present) {
+        (false, false) => Ok(None),
+        (true, false) => {
+            anyhow::bail!("Client certificate is configured but the private key is missing; both are required for client authentication")
+        }
+        (false, true) => {
+            anyhow::bail!("Client private key is configured but the certificate is missing; both are required for client authentication")
+        }
+        (true, true) => {
+            let cert_bytes = read_material("SCYLLA_TLS_CLIENT_CERT", "certs/client.crt")?;
+            let chain = certs_from_bytes(&cert_bytes)?;
+            if chain.is_empty() {
+                anyhow::bail!("No client certificates found in SCYLLA_TLS_CLIENT_CERT / certs/client.crt");
+            }
+
+            let key_bytes = read_material("SCYLLA_TLS_CLIENT_KEY", "certs/client.key")?;
+            let key = key_from_bytes(&key_bytes)?;
+
+            println!(
+                "✓ Loaded client certificate chain ({} cert(s))",
+                chain.len()
+            );
+
+            Ok(Some((chain, key)))
+        }
+    }
+}
+
+/// Parse a PEM file into a chain of DER-encoded certificates.
+pub fn load_cert_chain(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {path}"))?;
+    let mut reader = BufReader::new(file);
+    certs(&mut reader)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .with_context(|| format!("Failed to parse certificate(s) from {path}"))
+}
+
+/// Parse a PEM file into a single private key.
+pub fn load_private_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {path}"))?;
+    let mut reader = BufReader::new(file);
+    private_key(&mut reader)
+        .with_context(|| format!("Failed to parse private key from {path}"))?
+        .with_context(|| format!("No private key found in {path}"))
+}
+
+/// A verifier that accepts any server certificate without validation.
+///
+/// This exists purely for local testing against throwaway clusters; it defeats
+/// the point of TLS and must never be used against a real deployment. Signature
+/// checks are still delegated to the crypto provider so the handshake completes.
+#[derive(Debug)]
+struct InsecureServerVerifier {
+    provider: Arc<CryptoProvider>,
+}
+
+impl InsecureServerVerifier {
+    fn new() -> Self {
+        Self {
+            provider: default_crypto_provider(),
+        }
+    }
+}
+
+impl ServerCertVerifier for InsecureServerVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// A verifier that accepts the server only if its leaf certificate's SHA-256
+/// digest matches a fingerprint configured out-of-band.
+///
+/// The CA chain is deliberately ignored: this is for clusters whose CA we cannot
+/// place in a file but whose leaf fingerprint is known. The comparison is
+/// constant-time so a mismatch leaks no timing information about the expected pin.
+#[derive(Debug)]
+struct PinnedServerVerifier {
+    expected: [u8; 32],
+    provider: Arc<CryptoProvider>,
+}
+
+impl PinnedServerVerifier {
+    fn new(fingerprint_hex: &str) -> Result<Self> {
+        let expected = parse_hex_fingerprint(fingerprint_hex)?;
+        Ok(Self {
+            expected,
+            provider: default_crypto_provider(),
+        })
+    }
+
+    fn fingerprint_hex(&self) -> String {
+        hex_encode(&self.expected)
+    }
+}
+
+impl ServerCertVerifier for PinnedServerVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        let digest = Sha256::digest(end_entity.as_ref());
+        if constant_time_eq(digest.as_slice(), &self.expected) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::Other(rustls::OtherError(Arc::new(PinMismatch {
+                    expected: hex_encode(&self.expected),
+                    presented: hex_encode(digest.as_slice()),
+                }))),
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// Error carried by `CertificateError::Other` when the presented leaf
+/// certificate does not match the pinned fingerprint.
+#[derive(Debug)]
+struct PinMismatch {
+    expected: String,
+    presented: String,
+}
+
+impl std::fmt::Display for PinMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "server certificate fingerprint mismatch: expected {}, presented {}",
+            self.expected, self.presented
+        )
+    }
+}
+
+impl std::error::Error for PinMismatch {}
+
+/// The process-default crypto provider, installed when the first `ClientConfig`
+/// builder is created. Falls back to the aws-lc-rs provider if none is set yet.
+fn default_crypto_provider() -> Arc<CryptoProvider> {
+    CryptoProvider::get_default()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(rustls::crypto::aws_lc_rs::default_provider()))
+}
+
+/// Decode a 32-byte SHA-256 fingerprint from hex, tolerating ':' separators and
+/// surrounding whitespace as produced by `openssl x509 -fingerprint`.
+fn parse_hex_fingerprint(input: &str) -> Result<[u8; 32]> {
+    let cleaned: String = input
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .collect();
+    if cleaned.len() != 64 {
+        anyhow::bail!(
+            "Expected a 64-character (32-byte) hex SHA-256 fingerprint, got {} hex digits",
+            cleaned.len()
+        );
+    }
+    let mut out = [0u8; 32];
+    for (i, byte) in out.iter_mut().enumerate() {
+        let hi = &cleaned[2 * i..2 * i + 2];
+        *byte = u8::from_str_radix(hi, 16)
+            .with_context(|| format!("Invalid hex in fingerprint near '{hi}'"))?;
+    }
+    Ok(out)
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        s.push_str(&format!("{b:02x}"));
+    }
+    s
+}
+
+/// Constant-time byte-slice equality: always inspects every byte so the time
+/// taken does not reveal how many leading bytes matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut diff = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        diff |= x ^ y;
+    }
+    diff == 0
+}
@@ -1,59 +1,255 @@
 use anyhow::{Context, Result};
-use rustls::{ClientConfig, RootCertStore};
-use rustls_pemfile::certs;
 use scylla::client::execution_profile::ExecutionProfile;
 use scylla::client::session::Session;
 use scylla::client::session_builder::SessionBuilder;
 use scylla::policies::load_balancing;
 use scylla::policies::retry::DefaultRetryPolicy;
 use scylla::statement::Consistency;
-use std::fs::File;
-use std::io::BufReader;
 use std::sync::Arc;
 use std::time::Duration;
 
-fn load_rustls_config() -> Result<Arc<ClientConfig>> {
-    let mut root_store = RootCertStore::empty();
-
-    // Load CA certificate
-    let ca_file = File::open("certs/ca.crt")
-        .context("Failed to open CA certificate file. Make sure certs/ca.crt exists. Run ./generate-certs.sh if needed.")?;
-    let mut ca_reader = BufReader::new(ca_file);
-
-    // Read certificates from PEM file
-    let mut cert_count = 0;
-    for cert in certs(&mut ca_reader) {
-        let cert = cert.context("Failed to parse certificate from ca.crt")?;
-        root_store
-            .add(cert)
-            .context("Failed to add certificate to root store")?;
-        cert_count += 1;
+mod tls;
+
+// Preflight validation of the configured TLS material files (certs/ by default,
+// overridable via the SCYLLA_TLS_* variables), run before any live
+// connection is attempted. Each check prints a pass/fail line in the same style
+// as the startup banner; the function returns `false` if any check failed so the
+// caller can exit non-zero.
+fn run_preflight_checks() -> bool {
+    use x509_parser::prelude::*;
+
+    println!("Running TLS preflight checks...\n");
+    let mut ok = true;
+
+    let ca_path = tls::ca_path();
+    let cert_path = tls::client_cert_path();
+    let key_path = tls::client_key_path();
+
+    // 1. Parse the CA bundle.
+    let ca_der = match tls::load_cert_chain(&ca_path) {
+        Ok(chain) if !chain.is_empty() => {
+            println!("✓ Loaded {} CA certificate(s) from {ca_path}", chain.len());
+            Some(chain)
+        }
+        Ok(_) => {
+            println!("✗ No certificates found in {ca_path}");
+            ok = false;
+            None
+        }
+        Err(e) => {
+            println!("✗ Failed to parse {ca_path}: {e:#}");
+            ok = false;
+            None
+        }
+    };
+
+    // 2. Parse the client certificate chain.
+    let client_der = match tls::load_cert_chain(&cert_path) {
+        Ok(chain) if !chain.is_empty() => {
+            println!(
+                "✓ Loaded {} client certificate(s) from {cert_path}",
+                chain.len()
+            );
+            Some(chain)
+        }
+        Ok(_) => {
+            println!("✗ No certificates found in {cert_path}");
+            ok = false;
+            None
+        }
+        Err(e) => {
+            println!("✗ Failed to parse {cert_path}: {e:#}");
+            ok = false;
+            None
+        }
+    };
+
+    // 3. Parse the client private key.
+    let client_key = match tls::load_private_key(&key_path) {
+        Ok(key) => {
+            println!("✓ Loaded private key from {key_path}");
+            Some(key)
+        }
+        Err(e) => {
+            println!("✗ Failed to parse {key_path}: {e:#}");
+            ok = false;
+            None
+        }
+    };
+
+    // 4. The private key must actually correspond to the leaf certificate's
+    //    public key. Installing the key via `with_client_auth_cert` is *not*
+    //    enough: rustls only checks that the key parses, not that its public
+    //    half matches the certificate, so a valid-but-unrelated key would slip
+    //    through and fail only at the live handshake. Instead we sign a fixed
+    //    probe with the private key and verify the signature against the leaf
+    //    certificate's public key — correspondence holds iff that round-trips.
+    if let (Some(chain), Some(key)) = (client_der.as_ref(), client_key) {
+        match key_matches_certificate(&chain[0], key) {
+            Ok(true) => println!("✓ Client private key matches client certificate"),
+            Ok(false) => {
+                println!("✗ Client private key does not match client certificate");
+                ok = false;
+            }
+            Err(e) => {
+                println!("✗ Failed to check key/certificate correspondence: {e:#}");
+                ok = false;
+            }
+        }
     }
 
-    if cert_count == 0 {
-        anyhow::bail!("No certificates found in certs/ca.crt");
+    // 5. The leaf certificate must currently be within its validity window.
+    if let Some(chain) = client_der.as_ref() {
+        match X509Certificate::from_der(chain[0].as_ref()) {
+            Ok((_, leaf)) => {
+                let validity = leaf.validity();
+                if validity.is_valid() {
+                    println!(
+                        "✓ Client certificate is within its validity window (notAfter {})",
+                        validity.not_after
+                    );
+                } else {
+                    println!(
+                        "✗ Client certificate is not currently valid (notBefore {}, notAfter {})",
+                        validity.not_before, validity.not_after
+                    );
+                    ok = false;
+                }
+            }
+            Err(e) => {
+                println!("✗ Failed to decode client leaf certificate: {e}");
+                ok = false;
+            }
+        }
     }
 
-    println!(
-        "✓ Loaded {} CA certificate(s) from certs/ca.crt",
-        cert_count
-    );
+    // 6. The leaf certificate must chain to the configured CA. A valid chain may
+    //    be leaf -> intermediate(s) -> CA, so we walk from the leaf, following
+    //    issuers through any intermediates shipped in the client certificate
+    //    file, and accept once a certificate in the CA bundle signs the cert we
+    //    have reached. The CA bundle order is not assumed.
+    if let (Some(ca), Some(chain)) = (ca_der.as_ref(), client_der.as_ref()) {
+        let cas: Vec<_> = ca
+            .iter()
+            .filter_map(|d| X509Certificate::from_der(d.as_ref()).ok().map(|(_, c)| c))
+            .collect();
+        let intermediates: Vec<_> = chain[1..]
+            .iter()
+            .filter_map(|d| X509Certificate::from_der(d.as_ref()).ok().map(|(_, c)| c))
+            .collect();
 
-    // Build TLS config
-    let config = ClientConfig::builder()
-        .with_root_certificates(root_store)
-        .with_no_client_auth();
+        match X509Certificate::from_der(chain[0].as_ref()) {
+            Ok((_, leaf)) => {
+                let mut chained = false;
+                let mut current = &leaf;
+                let mut used = vec![false; intermediates.len()];
+                loop {
+                    // Accept if any CA in the bundle directly signed `current`.
+                    if cas
+                        .iter()
+                        .any(|c| current.verify_signature(Some(c.public_key())).is_ok())
+                    {
+                        chained = true;
+                        break;
+                    }
+                    // Otherwise follow an as-yet-unused intermediate that signed it.
+                    match intermediates.iter().enumerate().find(|(i, inter)| {
+                        !used[*i] && current.verify_signature(Some(inter.public_key())).is_ok()
+                    }) {
+                        Some((i, inter)) => {
+                            used[i] = true;
+                            current = inter;
+                        }
+                        None => break,
+                    }
+                }
+                if chained {
+                    println!("✓ Client certificate chains to CA in {ca_path}");
+                } else if cas.is_empty() {
+                    println!("✗ Failed to decode any CA certificate in {ca_path}");
+                    ok = false;
+                } else {
+                    println!("✗ Client certificate does not chain to any CA in {ca_path}");
+                    ok = false;
+                }
+            }
+            Err(e) => {
+                println!("✗ Failed to decode client leaf certificate: {e}");
+                ok = false;
+            }
+        }
+    }
 
-    Ok(Arc::new(config))
+    println!();
+    if ok {
+        println!("✓ All preflight checks passed");
+    } else {
+        println!("✗ One or more preflight checks failed");
+    }
+    ok
+}
+
+// Check that `key` is the private half of the leaf certificate's public key by
+// signing a fixed probe with the key and verifying the signature against the
+// certificate. Returns `Ok(false)` on a clean verification mismatch (unrelated
+// key) and `Err` only when the key cannot be loaded, no scheme is available, or
+// signing the probe fails.
+fn key_matches_certificate(
+    cert: &rustls::pki_types::CertificateDer<'_>,
+    key: rustls::pki_types::PrivateKeyDer<'static>,
+) -> Result<bool> {
+    use rustls::crypto::{verify_tls12_signature, CryptoProvider};
+    use rustls::DigitallySignedStruct;
+
+    let provider = CryptoProvider::get_default()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(rustls::crypto::aws_lc_rs::default_provider()));
+
+    let signing_key = provider
+        .key_provider
+        .load_private_key(key)
+        .context("Unsupported or malformed private key")?;
+    // Offer exactly the schemes the verifier can check, so the scheme the key
+    // picks is guaranteed to round-trip through `verify_tls12_signature` below.
+    let offered = provider
+        .signature_verification_algorithms
+        .supported_schemes();
+    let signer = signing_key
+        .choose_scheme(&offered)
+        .context("No signature scheme supported by this private key")?;
+
+    const PROBE: &[u8] = b"scylla-api preflight key/certificate correspondence probe";
+    let signature = signer
+        .sign(PROBE)
+        .context("Failed to sign probe with the private key")?;
+    let dss = DigitallySignedStruct::new(signer.scheme(), signature);
+
+    Ok(verify_tls12_signature(
+        PROBE,
+        cert,
+        &dss,
+        &provider.signature_verification_algorithms,
+    )
+    .is_ok())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // `try check` / `try --check` runs the offline TLS preflight and exits,
+    // without touching the network, so misconfigured material is caught early.
+    if std::env::args()
+        .skip(1)
+        .any(|a| a == "check" || a == "--check")
+    {
+        let ok = run_preflight_checks();
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
     println!("=== Starting Rust ScyllaDB Application ===\n");
 
-    // Build rustls TLS context
+    // Build rustls TLS context from the environment (None = plaintext).
     println!("Loading TLS configuration...");
-    let tls_config = load_rustls_config()?;
+    let tls_config = tls::build_client_config()?;
 
     println!("Building execution profile...");
     let execution_profile = ExecutionProfile::builder()
@@ -67,22 +263,28 @@ async fn main() -> Result<()> {
 
     // Connect to ScyllaDB cluster with TLS using rustls
     // Scylla listens on port 9042 with TLS enabled (not 9142)
-    println!("Connecting to ScyllaDB cluster with TLS...");
-    println!("  - Node 1: 127.0.0.2:9042 (TLS)");
-    println!("  - Node 2: 127.0.0.3:9042 (TLS)");
-    println!("  - Node 3: 127.0.0.4:9042 (TLS)");
-
-    let session: Session = SessionBuilder::new()
-        .known_node("127.0.0.2:9042") // TLS-enabled port for scylla1
-        .known_node("127.0.0.3:9042") // TLS-enabled port for scylla2
-        .known_node("127.0.0.4:9042") // TLS-enabled port for scylla3
-        .tls_context(Some(tls_config)) // Arc<ClientConfig> auto-converts to TlsContext
-        .default_execution_profile_handle(handle.clone())
+    println!("Connecting to ScyllaDB cluster...");
+    println!("  - Node 1: 127.0.0.2:9042");
+    println!("  - Node 2: 127.0.0.3:9042");
+    println!("  - Node 3: 127.0.0.4:9042");
+
+    let mut builder = SessionBuilder::new()
+        .known_node("127.0.0.2:9042") // scylla1
+        .known_node("127.0.0.3:9042") // scylla2
+        .known_node("127.0.0.4:9042") // scylla3
+        .default_execution_profile_handle(handle.clone());
+
+    // Enable TLS only when the environment asked for it.
+    if let Some(config) = tls_config {
+        builder = builder.tls_context(Some(config));
+    }
+
+    let session: Session = builder
         .build()
         .await
         .context("Failed to connect to ScyllaDB cluster. Ensure:\n  1. Docker containers are running (docker-compose ps)\n  2. Certificates are generated (./generate-certs.sh)\n  3. All nodes are healthy (docker exec rust-application-scylla1-1 nodetool status)")?;
 
-    println!("✓ Connected to ScyllaDB cluster with TLS\n");
+    println!("✓ Connected to ScyllaDB cluster\n");
 
     // Create keyspace and table
     println!("Setting up database schema...");